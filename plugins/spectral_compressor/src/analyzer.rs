@@ -0,0 +1,67 @@
+// Spectral Compressor: an FFT based compressor
+// Copyright (C) 2021-2023 Robbert van der Helm
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Data types shared between the audio thread and the editor for the spectrum analyzer display.
+//! The audio thread fills in an [`AnalyzerData`] object once per FFT block and ships it over to
+//! the editor through a lock-free triple buffer, so drawing the UI can never block or stall audio
+//! processing.
+
+use triple_buffer::TripleBuffer;
+
+/// The number of bins the analyzer keeps track of and sends to the editor. The editor is
+/// responsible for resampling these to one value per horizontal pixel, interpolating between bins
+/// when the spectrum is drawn wider than this.
+pub const NUM_BINS: usize = 512;
+
+/// The data sent from the audio thread to the editor whenever an FFT block completes. Everything in here
+/// is already converted to the display's units (dBFS and linear bin indices) so the editor does
+/// not need to know anything about the FFT size or the compressor's internals.
+#[derive(Debug, Clone, Copy)]
+pub struct AnalyzerData {
+    /// The magnitude spectrum of the input signal, in dBFS, for `NUM_BINS` logarithmically spaced
+    /// frequencies between 20 Hz and 20 kHz.
+    pub spectrum: [f32; NUM_BINS],
+    /// The current upwards/downwards threshold curve, in dBFS, at the same frequencies as
+    /// `spectrum`.
+    pub threshold: [f32; NUM_BINS],
+    /// The gain reduction (negative) or gain increase (positive) currently applied to each bin, in
+    /// dB.
+    pub gain_reduction: [f32; NUM_BINS],
+}
+
+impl Default for AnalyzerData {
+    fn default() -> Self {
+        Self {
+            spectrum: [-120.0; NUM_BINS],
+            threshold: [-120.0; NUM_BINS],
+            gain_reduction: [0.0; NUM_BINS],
+        }
+    }
+}
+
+/// The producing end of the triple buffer used to ship [`AnalyzerData`] from the audio thread to
+/// the editor. This is written to at most once per processed audio buffer, after an FFT block
+/// completes.
+pub type AnalyzerInput = triple_buffer::Input<AnalyzerData>;
+/// The consuming end of the triple buffer used to ship [`AnalyzerData`] to the editor. The editor
+/// reads from this every time it draws the spectrum view, and when hit-testing a mouse down.
+pub type AnalyzerOutput = triple_buffer::Output<AnalyzerData>;
+
+/// Creates a new triple buffer for sending [`AnalyzerData`] from the audio thread to the editor
+/// without any locking.
+pub fn analyzer_buffer() -> (AnalyzerInput, AnalyzerOutput) {
+    TripleBuffer::default().split()
+}
@@ -14,25 +14,140 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+mod analyzer;
+pub(crate) mod theme;
+
 use nih_plug::prelude::*;
 use nih_plug_vizia::vizia::prelude::*;
+use nih_plug_vizia::widgets::param_base::ParamWidgetBase;
 use nih_plug_vizia::widgets::*;
 use nih_plug_vizia::{assets, create_vizia_editor, ViziaState, ViziaTheming};
 use std::sync::Arc;
 
+use crate::analyzer::AnalyzerOutput;
 use crate::{SpectralCompressor, SpectralCompressorParams};
 
-// I couldn't get `LayoutType::Grid` to work as expected, so we'll fake a 4x4 grid with
-// hardcoded column widths
-const COLUMN_WIDTH: Units = Pixels(330.0);
-const DARKER_GRAY: Color = Color::rgb(0x69, 0x69, 0x69);
+use self::analyzer::SpectrumView;
+use self::theme::EditorTheme;
+
+/// The width, in pixels, a column needs at minimum before another one is allowed onto the same
+/// row. Below this the next breakpoint's column count is used instead.
+const COLUMN_MIN_WIDTH: f32 = 300.0;
+/// The four sections of parameters, reflowed into as many columns as fit the current window width
+/// (see [`columns_for_width()`]).
+const NUM_SECTIONS: usize = 4;
 
 #[derive(Lens)]
 struct Data {
     params: Arc<SpectralCompressorParams>,
+    /// How many of the four parameter sections are placed on the same row. Recomputed whenever the
+    /// editor's width changes, see [`GridEvent::WidthChanged`].
+    columns: usize,
+    /// Whether the About overlay is currently shown, toggled from the info button in the header.
+    show_about: bool,
+}
+
+enum GridEvent {
+    WidthChanged(f32),
+}
+
+enum AboutEvent {
+    Toggle,
+}
+
+impl Model for Data {
+    fn event(&mut self, _cx: &mut EventContext, event: &mut Event) {
+        event.map(|grid_event, _| match grid_event {
+            GridEvent::WidthChanged(width) => self.columns = columns_for_width(*width),
+        });
+
+        event.map(|about_event, _| match about_event {
+            AboutEvent::Toggle => self.show_about = !self.show_about,
+        });
+    }
+}
+
+/// Picks how many of the four parameter sections fit on one row at `width` pixels, using 4 → 2 →
+/// 1 breakpoints so the editor stays usable as the user shrinks the window with the
+/// [`ResizeHandle`].
+fn columns_for_width(width: f32) -> usize {
+    for columns in [4, 2, 1] {
+        if width >= COLUMN_MIN_WIDTH * columns as f32 {
+            return columns;
+        }
+    }
+
+    1
+}
+
+/// An invisible view that watches the editor's width and fires a [`GridEvent::WidthChanged`]
+/// whenever it changes, so the parameter grid can reflow in response to the user resizing the
+/// window.
+struct WidthObserver;
+
+impl WidthObserver {
+    fn new(cx: &mut Context) -> Handle<Self> {
+        Self.build(cx, |_| ())
+    }
+}
+
+impl View for WidthObserver {
+    fn element(&self) -> Option<&'static str> {
+        Some("width-observer")
+    }
+
+    fn geometry_changed(&mut self, cx: &mut EventContext, geo: GeoChanged) {
+        if geo.contains(GeoChanged::WIDTH_CHANGED) {
+            cx.emit(GridEvent::WidthChanged(cx.bounds().w));
+        }
+    }
+}
+
+/// A small button in the header that cycles through the built-in [`EditorTheme`]s, writing the
+/// choice through to the persisted `editor_theme` parameter so it's saved with the project.
+struct ThemeButton {
+    param_base: ParamWidgetBase,
+}
+
+impl ThemeButton {
+    fn new<L>(cx: &mut Context, params: L) -> Handle<Self>
+    where
+        L: Lens<Target = Arc<SpectralCompressorParams>> + Clone,
+    {
+        Self {
+            param_base: ParamWidgetBase::new(cx, params, |params| &params.editor_theme),
+        }
+        .build(cx, |cx| {
+            Label::new(cx, "Theme").class("label");
+        })
+    }
 }
 
-impl Model for Data {}
+impl View for ThemeButton {
+    fn element(&self) -> Option<&'static str> {
+        Some("theme-button")
+    }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|window_event, meta| {
+            if let WindowEvent::MouseDown(MouseButton::Left) = window_event {
+                // Enum parameters are normalized as `index / (num_steps - 1)`, so cycling needs to
+                // go through the integer index instead of adding a fixed step to the normalized
+                // value: floating-point drift would otherwise make some indices unreachable.
+                let last_index = EditorTheme::NUM_THEMES - 1;
+                let current = self.param_base.unmodulated_normalized_value();
+                let current_index = (current * last_index as f32).round() as usize;
+                let next_index = (current_index + 1) % EditorTheme::NUM_THEMES;
+                let next = next_index as f32 / last_index as f32;
+
+                self.param_base.begin_set_parameter(cx);
+                self.param_base.set_normalized_value(cx, next);
+                self.param_base.end_set_parameter(cx);
+                meta.consume();
+            }
+        });
+    }
+}
 
 // Makes sense to also define this here, makes it a bit easier to keep track of
 pub(crate) fn default_state() -> Arc<ViziaState> {
@@ -42,17 +157,32 @@ pub(crate) fn default_state() -> Arc<ViziaState> {
 pub(crate) fn create(
     params: Arc<SpectralCompressorParams>,
     editor_state: Arc<ViziaState>,
+    analyzer_data: AnalyzerOutput,
 ) -> Option<Box<dyn Editor>> {
     create_vizia_editor(editor_state, ViziaTheming::Custom, move |cx, _| {
         assets::register_noto_sans_light(cx);
         assets::register_noto_sans_thin(cx);
 
+        let (width, _) = editor_state.size();
         Data {
             params: params.clone(),
+            columns: columns_for_width(width as f32),
+            show_about: false,
         }
         .build(cx);
 
+        // Reload the stylesheet whenever the persisted theme parameter changes, including the
+        // very first time this closure runs.
+        Binding::new(
+            cx,
+            Data::params.map(|p| p.editor_theme.value()),
+            |cx, theme| {
+                cx.add_theme(theme::stylesheet(theme.get(cx)));
+            },
+        );
+
         ResizeHandle::new(cx);
+        WidthObserver::new(cx).width(Stretch(1.0)).height(Pixels(0.0));
 
         VStack::new(cx, |cx| {
             HStack::new(cx, |cx| {
@@ -70,99 +200,204 @@ pub(crate) fn create(
                         }
                     });
                 Label::new(cx, SpectralCompressor::VERSION)
-                    .color(DARKER_GRAY)
+                    .class("label")
                     .top(Stretch(1.0))
                     .bottom(Pixels(4.0))
                     .left(Pixels(2.0));
+
+                ThemeButton::new(cx, Data::params)
+                    .top(Stretch(1.0))
+                    .bottom(Pixels(4.0))
+                    .left(Pixels(10.0));
+
+                Label::new(cx, "\u{24D8}")
+                    .class("label")
+                    .top(Stretch(1.0))
+                    .bottom(Pixels(4.0))
+                    .left(Pixels(10.0))
+                    .on_mouse_down(|cx, _| cx.emit(AboutEvent::Toggle));
             })
             .height(Pixels(30.0))
             .right(Pixels(-17.0))
             .bottom(Pixels(-5.0))
             .top(Pixels(10.0));
 
-            HStack::new(cx, |cx| {
-                make_column(cx, "Globals", |cx| {
-                    GenericUi::new(cx, Data::params.map(|p| p.global.clone()));
-                });
+            SpectrumView::new(cx, analyzer_data, Data::params)
+                .height(Pixels(120.0))
+                .width(Stretch(1.0));
 
-                make_column(cx, "Threshold", |cx| {
-                    GenericUi::new(cx, Data::params.map(|p| p.threshold.clone()));
-
-                    Label::new(
-                        cx,
-                        "Parameter ranges and overal gain staging are still subject to change. If \
-                         you use this in a project, make sure to bounce things to audio just in \
-                         case they'll sound different later.",
-                    )
-                    .font_size(11.0)
-                    .left(Pixels(15.0))
-                    .right(Pixels(8.0))
-                    // The column isn't tall enough without this, for some reason
-                    .bottom(Pixels(20.0))
-                    .width(Stretch(1.0));
-                });
-            })
-            .height(Auto)
-            .width(Stretch(1.0));
-
-            HStack::new(cx, |cx| {
-                make_column(cx, "Upwards", |cx| {
-                    // We don't want to show the 'Upwards' prefix here, but it should still be in
-                    // the parameter name so the parameter list makes sense
-                    let upwards_compressor_params =
-                        Data::params.map(|p| p.compressors.upwards.clone());
-                    GenericUi::new_custom(
-                        cx,
-                        upwards_compressor_params.clone(),
-                        move |cx, param_ptr| {
-                            let upwards_compressor_params = upwards_compressor_params.clone();
-                            HStack::new(cx, move |cx| {
-                                Label::new(
-                                    cx,
-                                    unsafe { param_ptr.name() }
-                                        .strip_prefix("Upwards ")
-                                        .expect("Expected parameter name prefix, this is a bug"),
-                                )
-                                .class("label");
-
-                                GenericUi::draw_widget(cx, upwards_compressor_params, param_ptr);
-                            })
-                            .class("row");
-                        },
-                    );
-                });
-
-                make_column(cx, "Downwards", |cx| {
-                    let downwards_compressor_params =
-                        Data::params.map(|p| p.compressors.downwards.clone());
-                    GenericUi::new_custom(
-                        cx,
-                        downwards_compressor_params.clone(),
-                        move |cx, param_ptr| {
-                            let downwards_compressor_params = downwards_compressor_params.clone();
-                            HStack::new(cx, move |cx| {
-                                Label::new(
-                                    cx,
-                                    unsafe { param_ptr.name() }
-                                        .strip_prefix("Downwards ")
-                                        .expect("Expected parameter name prefix, this is a bug"),
-                                )
-                                .class("label");
-
-                                GenericUi::draw_widget(cx, downwards_compressor_params, param_ptr);
-                            })
-                            .class("row");
-                        },
-                    );
-                });
-            })
-            .height(Auto)
-            .width(Stretch(1.0));
+            Binding::new(cx, Data::columns, |cx, columns| {
+                build_parameter_grid(cx, columns.get(cx));
+            });
         })
         .row_between(Pixels(15.0))
         .child_left(Stretch(1.0))
         .child_right(Stretch(1.0));
+
+        Binding::new(cx, Data::show_about, |cx, show_about| {
+            if show_about.get(cx) {
+                build_about_overlay(cx);
+            }
+        });
+    })
+}
+
+/// Builds the "About" overlay shown on top of the rest of the editor, with version, license, and
+/// author information. Clicking the dimmed background closes it again.
+fn build_about_overlay(cx: &mut Context) {
+    VStack::new(cx, |cx| {
+        VStack::new(cx, |cx| {
+            Label::new(cx, "Spectral Compressor")
+                .font_family(vec![FamilyOwned::Name(String::from(
+                    assets::NOTO_SANS_THIN,
+                ))])
+                .font_size(24.0);
+
+            Label::new(cx, SpectralCompressor::VERSION).class("label");
+
+            Label::new(
+                cx,
+                &format!(
+                    "By {}{}",
+                    SpectralCompressor::VENDOR,
+                    if cfg!(debug_assertions) {
+                        " (debug build)"
+                    } else {
+                        ""
+                    },
+                ),
+            )
+            .class("label");
+
+            Label::new(
+                cx,
+                "Copyright (C) 2021-2023 Robbert van der Helm\n\
+                 Licensed under the GNU General Public License, version 3 or later.",
+            )
+            .class("label")
+            .width(Stretch(1.0));
+
+            Label::new(cx, SpectralCompressor::URL)
+                .class("label")
+                .on_mouse_down(|_, _| {
+                    let result = open::that(SpectralCompressor::URL);
+                    if cfg!(debug) && result.is_err() {
+                        nih_debug_assert_failure!("Failed to open web browser: {:?}", result);
+                    }
+                });
+        })
+        .row_between(Pixels(8.0))
+        .child_space(Pixels(20.0))
+        .width(Pixels(320.0))
+        .height(Auto)
+        .background_color(Color::rgb(0x28, 0x28, 0x28))
+        // Swallow clicks on the card itself so they don't fall through to the background below
+        // and close the overlay.
+        .on_mouse_down(|_, _| {});
     })
+    .background_color(Color::rgba(0x00, 0x00, 0x00, 0xA0))
+    .child_space(Stretch(1.0))
+    .position_type(PositionType::SelfDirected)
+    .space(Pixels(0.0))
+    .width(Stretch(1.0))
+    .height(Stretch(1.0))
+    .on_mouse_down(|cx, _| cx.emit(AboutEvent::Toggle));
+}
+
+/// Lays out the "Globals", "Threshold", "Upwards", and "Downwards" sections into rows of
+/// `columns` columns each, reflowing them to fit the editor's current width.
+fn build_parameter_grid(cx: &mut Context, columns: usize) {
+    let sections: [(&str, Box<dyn Fn(&mut Context)>); NUM_SECTIONS] = [
+        (
+            "Globals",
+            Box::new(|cx| {
+                GenericUi::new(cx, Data::params.map(|p| p.global.clone()));
+            }),
+        ),
+        (
+            "Threshold",
+            Box::new(|cx| {
+                GenericUi::new(cx, Data::params.map(|p| p.threshold.clone()));
+
+                Label::new(
+                    cx,
+                    "Parameter ranges and overal gain staging are still subject to change. If \
+                     you use this in a project, make sure to bounce things to audio just in \
+                     case they'll sound different later.",
+                )
+                .font_size(11.0)
+                .left(Pixels(15.0))
+                .right(Pixels(8.0))
+                // The column isn't tall enough without this, for some reason
+                .bottom(Pixels(20.0))
+                .width(Stretch(1.0));
+            }),
+        ),
+        (
+            "Upwards",
+            Box::new(|cx| {
+                // We don't want to show the 'Upwards' prefix here, but it should still be in
+                // the parameter name so the parameter list makes sense
+                let upwards_compressor_params =
+                    Data::params.map(|p| p.compressors.upwards.clone());
+                GenericUi::new_custom(
+                    cx,
+                    upwards_compressor_params.clone(),
+                    move |cx, param_ptr| {
+                        let upwards_compressor_params = upwards_compressor_params.clone();
+                        HStack::new(cx, move |cx| {
+                            Label::new(
+                                cx,
+                                unsafe { param_ptr.name() }
+                                    .strip_prefix("Upwards ")
+                                    .expect("Expected parameter name prefix, this is a bug"),
+                            )
+                            .class("label");
+
+                            GenericUi::draw_widget(cx, upwards_compressor_params, param_ptr);
+                        })
+                        .class("row");
+                    },
+                );
+            }),
+        ),
+        (
+            "Downwards",
+            Box::new(|cx| {
+                let downwards_compressor_params =
+                    Data::params.map(|p| p.compressors.downwards.clone());
+                GenericUi::new_custom(
+                    cx,
+                    downwards_compressor_params.clone(),
+                    move |cx, param_ptr| {
+                        let downwards_compressor_params = downwards_compressor_params.clone();
+                        HStack::new(cx, move |cx| {
+                            Label::new(
+                                cx,
+                                unsafe { param_ptr.name() }
+                                    .strip_prefix("Downwards ")
+                                    .expect("Expected parameter name prefix, this is a bug"),
+                            )
+                            .class("label");
+
+                            GenericUi::draw_widget(cx, downwards_compressor_params, param_ptr);
+                        })
+                        .class("row");
+                    },
+                );
+            }),
+        ),
+    ];
+
+    for row in sections.chunks(columns.max(1)) {
+        HStack::new(cx, |cx| {
+            for (title, contents) in row {
+                make_column(cx, title, |cx| contents(cx));
+            }
+        })
+        .height(Auto)
+        .width(Stretch(1.0));
+    }
 }
 
 fn make_column(cx: &mut Context, title: &str, contents: impl FnOnce(&mut Context)) {
@@ -179,6 +414,6 @@ fn make_column(cx: &mut Context, title: &str, contents: impl FnOnce(&mut Context
 
         contents(cx);
     })
-    .width(COLUMN_WIDTH)
+    .width(Stretch(1.0))
     .height(Auto);
 }
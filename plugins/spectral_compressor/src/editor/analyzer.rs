@@ -0,0 +1,442 @@
+// Spectral Compressor: an FFT based compressor
+// Copyright (C) 2021-2023 Robbert van der Helm
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use nih_plug_vizia::vizia::prelude::*;
+use nih_plug_vizia::vizia::vg;
+use nih_plug_vizia::widgets::param_base::ParamWidgetBase;
+use std::cell::{Cell, RefCell};
+use std::sync::Arc;
+
+use crate::analyzer::{AnalyzerData, AnalyzerOutput, NUM_BINS};
+use crate::SpectralCompressorParams;
+
+/// The draggable control points overlaid on the widget. `ThresholdOffset` sits directly on the
+/// threshold curve, in the same dB space it's drawn in. The ratio parameters don't have a dB
+/// value of their own (there's no separate upwards/downwards curve plotted), so they're instead
+/// drawn as small handles in the dedicated strip below the graph returned by
+/// [`ratio_track_bounds()`], to avoid implying they sit on a curve that isn't actually there.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DragTarget {
+    ThresholdOffset,
+    UpwardsRatio,
+    DownwardsRatio,
+}
+
+impl DragTarget {
+    /// All draggable targets together with the X position they're drawn and hit-tested at, as a
+    /// fraction of their bounds' width (the plot's for `ThresholdOffset`, the ratio track's for
+    /// the other two).
+    const ALL: [(DragTarget, f32); 3] = [
+        (DragTarget::ThresholdOffset, 0.5),
+        (DragTarget::UpwardsRatio, 0.3),
+        (DragTarget::DownwardsRatio, 0.7),
+    ];
+}
+
+/// How close the cursor needs to be to a node, in pixels, for a mouse down to start dragging it
+/// instead of falling back to dragging the curve as a whole.
+const NODE_HIT_RADIUS: f32 = 8.0;
+/// How close the cursor needs to be to the threshold curve itself, in pixels, for a mouse down to
+/// start dragging the global threshold offset.
+const CURVE_HIT_TOLERANCE: f32 = 6.0;
+
+/// The lowest magnitude shown on the Y axis, in dBFS.
+const MIN_MAGNITUDE_DB: f32 = -80.0;
+/// The highest magnitude shown on the Y axis, in dBFS.
+const MAX_MAGNITUDE_DB: f32 = 10.0;
+/// The plain value range of `SpectralCompressorParams::threshold::threshold_offset_db`, kept in
+/// sync with its `FloatRange` so the draggable node lines up with the curve it controls.
+const THRESHOLD_OFFSET_RANGE_DB: (f32, f32) = (-50.0, 50.0);
+/// The height, in pixels, reserved at the bottom of the widget for the upwards/downwards ratio
+/// handles, kept visually separate from the dB-labeled graph above since ratio isn't a dB
+/// quantity and there's no curve for it to sit on.
+const RATIO_TRACK_HEIGHT: f32 = 16.0;
+/// The vertical gap between the graph and the ratio track.
+const RATIO_TRACK_GAP: f32 = 6.0;
+/// How much the per-pixel peak hold decays per drawn frame. This is not time based since we don't
+/// have a reliable delta time in `draw()`, but at the redraw rate vizia runs at this still looks
+/// like a smooth decay.
+const PEAK_HOLD_DECAY_DB: f32 = 0.3;
+
+/// The portion of the widget's bounds the spectrum/threshold/gain-reduction graph is drawn in,
+/// i.e. everything above the ratio track.
+fn plot_bounds(bounds: BoundingBox) -> BoundingBox {
+    BoundingBox {
+        x: bounds.x,
+        y: bounds.y,
+        w: bounds.w,
+        h: (bounds.h - RATIO_TRACK_HEIGHT - RATIO_TRACK_GAP).max(0.0),
+    }
+}
+
+/// The portion of the widget's bounds the upwards/downwards ratio handles are drawn in, i.e. the
+/// strip at the very bottom.
+fn ratio_track_bounds(bounds: BoundingBox) -> BoundingBox {
+    BoundingBox {
+        x: bounds.x,
+        y: bounds.y + bounds.h - RATIO_TRACK_HEIGHT,
+        w: bounds.w,
+        h: RATIO_TRACK_HEIGHT,
+    }
+}
+
+/// A widget that shows the input signal's spectrum together with the current threshold curve and
+/// the per-bin gain reduction, similar to the displays found on most spectral/EQ style
+/// compressors. The underlying data is produced on the audio thread and shipped over through a
+/// lock-free triple buffer, see [`crate::analyzer`]. The bins in [`AnalyzerData`] are already
+/// spaced logarithmically between 20 Hz and 20 kHz, so they can be decimated
+/// directly to horizontal pixels without any further frequency mapping.
+pub struct SpectrumView {
+    analyzer_data: RefCell<AnalyzerOutput>,
+    /// Decimated, per-pixel peak-held spectrum magnitudes from the previous frame. Empty until the
+    /// first call to `draw()`, and resized whenever the widget's width changes.
+    peak_held_magnitudes: RefCell<Vec<f32>>,
+
+    threshold_offset_param: ParamWidgetBase,
+    upwards_ratio_param: ParamWidgetBase,
+    downwards_ratio_param: ParamWidgetBase,
+    /// Set while one of the [`DragTarget`]s is being dragged, either from its node or, for
+    /// `ThresholdOffset`, from the threshold curve itself.
+    drag_target: Cell<Option<DragTarget>>,
+}
+
+impl SpectrumView {
+    pub fn new<L>(cx: &mut Context, analyzer_data: AnalyzerOutput, params: L) -> Handle<Self>
+    where
+        L: Lens<Target = Arc<SpectralCompressorParams>> + Clone,
+    {
+        Self {
+            analyzer_data: RefCell::new(analyzer_data),
+            peak_held_magnitudes: RefCell::new(Vec::new()),
+
+            threshold_offset_param: ParamWidgetBase::new(cx, params.clone(), |params| {
+                &params.threshold.threshold_offset_db
+            }),
+            upwards_ratio_param: ParamWidgetBase::new(cx, params.clone(), |params| {
+                &params.compressors.upwards.ratio
+            }),
+            downwards_ratio_param: ParamWidgetBase::new(cx, params, |params| {
+                &params.compressors.downwards.ratio
+            }),
+            drag_target: Cell::new(None),
+        }
+        .build(cx, |_| ())
+    }
+
+    /// Returns the [`ParamWidgetBase`] backing a given drag target.
+    fn param_base(&self, target: DragTarget) -> &ParamWidgetBase {
+        match target {
+            DragTarget::ThresholdOffset => &self.threshold_offset_param,
+            DragTarget::UpwardsRatio => &self.upwards_ratio_param,
+            DragTarget::DownwardsRatio => &self.downwards_ratio_param,
+        }
+    }
+
+    /// Finds the node within [`NODE_HIT_RADIUS`] pixels of `(mouse_x, mouse_y)`, if any. Nodes are
+    /// positioned at their fixed X fraction of `bounds`; `ThresholdOffset` sits on the same dB axis
+    /// the curves are drawn on and the ratio nodes sit in the ratio track below it, see
+    /// [`Self::node_position()`].
+    fn hit_test_node(&self, bounds: BoundingBox, mouse_x: f32, mouse_y: f32) -> Option<DragTarget> {
+        DragTarget::ALL
+            .into_iter()
+            .map(|(target, x_fraction)| {
+                let (x, y) = self.node_position(bounds, target, x_fraction);
+                let distance = ((mouse_x - x).powi(2) + (mouse_y - y).powi(2)).sqrt();
+                (target, distance)
+            })
+            .filter(|(_, distance)| *distance <= NODE_HIT_RADIUS)
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(target, _)| target)
+    }
+
+    /// Whether `(mouse_x, mouse_y)` is within [`CURVE_HIT_TOLERANCE`] pixels of the threshold
+    /// curve itself, used as the fallback for dragging the global threshold offset when the click
+    /// misses every node in [`DragTarget::ALL`].
+    fn hit_test_curve(&self, bounds: BoundingBox, mouse_x: f32, mouse_y: f32) -> bool {
+        let data = *self.analyzer_data.borrow_mut().read();
+
+        let plot = plot_bounds(bounds);
+        let num_pixels = plot.w.round().max(1.0) as usize;
+        let decimated_threshold = decimate_to_pixels(&data.threshold, num_pixels);
+        let pixel = ((mouse_x - plot.x).round() as isize)
+            .clamp(0, num_pixels.saturating_sub(1) as isize) as usize;
+
+        match decimated_threshold.get(pixel) {
+            Some(&threshold_db) => (mouse_y - y_for_db(plot, threshold_db)).abs() <= CURVE_HIT_TOLERANCE,
+            None => false,
+        }
+    }
+
+    /// `ThresholdOffset`'s current value, expressed in the same dB space the threshold curve is
+    /// drawn in, so the node can be placed directly on that curve.
+    fn threshold_db_value(&self) -> f32 {
+        let normalized = self.param_base(DragTarget::ThresholdOffset).unmodulated_normalized_value();
+        let (min, max) = THRESHOLD_OFFSET_RANGE_DB;
+
+        min + normalized * (max - min)
+    }
+
+    /// The inverse of [`Self::threshold_db_value()`]: converts a dB value on the plot's axis back
+    /// to the threshold offset parameter's normalized range.
+    fn normalized_for_threshold_db(db: f32) -> f32 {
+        let (min, max) = THRESHOLD_OFFSET_RANGE_DB;
+
+        ((db - min) / (max - min)).clamp(0.0, 1.0)
+    }
+
+    /// The pixel position of a node. `ThresholdOffset` is placed on the plot, on the same dB axis
+    /// the threshold curve is drawn in; the ratio nodes are placed in the separate, non-dB
+    /// [`RATIO_TRACK_HEIGHT`]-tall strip below it, positioned by their plain normalized value.
+    fn node_position(&self, bounds: BoundingBox, target: DragTarget, x_fraction: f32) -> (f32, f32) {
+        match target {
+            DragTarget::ThresholdOffset => {
+                let plot = plot_bounds(bounds);
+                let x = plot.x + plot.w * x_fraction;
+                let y = y_for_db(plot, self.threshold_db_value());
+
+                (x, y)
+            }
+            DragTarget::UpwardsRatio | DragTarget::DownwardsRatio => {
+                let track = ratio_track_bounds(bounds);
+                let normalized = self.param_base(target).unmodulated_normalized_value();
+                let x = track.x + track.w * x_fraction;
+                let y = track.y + track.h * (1.0 - normalized);
+
+                (x, y)
+            }
+        }
+    }
+}
+
+/// Maps a dB value to a Y coordinate within `bounds`, using the same [`MIN_MAGNITUDE_DB`]..
+/// [`MAX_MAGNITUDE_DB`] axis the spectrum, threshold, and gain reduction curves are drawn on.
+fn y_for_db(bounds: BoundingBox, db: f32) -> f32 {
+    let t = (db - MIN_MAGNITUDE_DB) / (MAX_MAGNITUDE_DB - MIN_MAGNITUDE_DB);
+    bounds.y + bounds.h * (1.0 - t.clamp(0.0, 1.0))
+}
+
+/// The inverse of [`y_for_db()`]: maps a Y coordinate within `bounds` back to a dB value on the
+/// same axis.
+fn db_for_y(bounds: BoundingBox, y: f32) -> f32 {
+    let t = 1.0 - ((y - bounds.y) / bounds.h).clamp(0.0, 1.0);
+    MIN_MAGNITUDE_DB + t * (MAX_MAGNITUDE_DB - MIN_MAGNITUDE_DB)
+}
+
+impl View for SpectrumView {
+    fn element(&self) -> Option<&'static str> {
+        Some("spectrum-view")
+    }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|window_event, meta| match window_event {
+            WindowEvent::MouseDown(MouseButton::Left) => {
+                let bounds = cx.bounds();
+                let (mouse_x, mouse_y) = (cx.mouse.cursorx, cx.mouse.cursory);
+
+                // Clicking a node drags that node specifically. Missing every node but still
+                // landing on the threshold curve itself falls back to dragging the whole curve,
+                // i.e. the global threshold offset.
+                let target = self.hit_test_node(bounds, mouse_x, mouse_y).or_else(|| {
+                    self.hit_test_curve(bounds, mouse_x, mouse_y)
+                        .then_some(DragTarget::ThresholdOffset)
+                });
+
+                if let Some(target) = target {
+                    self.drag_target.set(Some(target));
+                    self.param_base(target).begin_set_parameter(cx);
+                    cx.capture();
+                    meta.consume();
+                }
+            }
+            WindowEvent::MouseMove(_, mouse_y) => {
+                if let Some(target) = self.drag_target.get() {
+                    let bounds = cx.bounds();
+                    let normalized = match target {
+                        DragTarget::ThresholdOffset => {
+                            let db = db_for_y(plot_bounds(bounds), *mouse_y);
+                            Self::normalized_for_threshold_db(db)
+                        }
+                        DragTarget::UpwardsRatio | DragTarget::DownwardsRatio => {
+                            let track = ratio_track_bounds(bounds);
+                            (1.0 - ((*mouse_y - track.y) / track.h)).clamp(0.0, 1.0)
+                        }
+                    };
+                    self.param_base(target).set_normalized_value(cx, normalized);
+                    meta.consume();
+                }
+            }
+            WindowEvent::MouseUp(MouseButton::Left) => {
+                if let Some(target) = self.drag_target.take() {
+                    self.param_base(target).end_set_parameter(cx);
+                    cx.release();
+                    meta.consume();
+                }
+            }
+            _ => {}
+        });
+    }
+
+    fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        let bounds = cx.bounds();
+        if bounds.w <= 0.0 || bounds.h <= 0.0 {
+            return;
+        }
+
+        let data = *self.analyzer_data.borrow_mut().read();
+
+        let plot = plot_bounds(bounds);
+        let num_pixels = plot.w.round().max(1.0) as usize;
+        let decimated_spectrum = decimate_to_pixels(&data.spectrum, num_pixels);
+        let decimated_threshold = decimate_to_pixels(&data.threshold, num_pixels);
+        let decimated_gain_reduction = decimate_to_pixels(&data.gain_reduction, num_pixels);
+        let held_spectrum = self.update_peak_hold(&decimated_spectrum);
+
+        let x_for_pixel = |pixel: usize| plot.x + pixel as f32;
+        let y_for_pixel_db = |db: f32| y_for_db(plot, db);
+
+        draw_gain_reduction_band(
+            canvas,
+            &decimated_spectrum,
+            &decimated_gain_reduction,
+            x_for_pixel,
+            y_for_pixel_db,
+        );
+        draw_polyline(
+            canvas,
+            &held_spectrum,
+            x_for_pixel,
+            y_for_pixel_db,
+            vg::Color::rgbf(0.7, 0.7, 0.7),
+        );
+        draw_polyline(
+            canvas,
+            &decimated_threshold,
+            x_for_pixel,
+            y_for_pixel_db,
+            vg::Color::rgbf(1.0, 0.8, 0.2),
+        );
+
+        let (x, y) = self.node_position(bounds, DragTarget::ThresholdOffset, 0.5);
+        let mut threshold_node = vg::Path::new();
+        threshold_node.circle(x, y, 4.0);
+        canvas.fill_path(&threshold_node, &vg::Paint::color(vg::Color::rgbf(1.0, 0.8, 0.2)));
+
+        // The ratio handles live in their own strip below the plot since ratio isn't a dB
+        // quantity and there's no curve here for them to sit on.
+        for (target, x_fraction) in DragTarget::ALL {
+            if target == DragTarget::ThresholdOffset {
+                continue;
+            }
+
+            let (x, y) = self.node_position(bounds, target, x_fraction);
+            let mut handle = vg::Path::new();
+            handle.rect(x - 3.0, y - 3.0, 6.0, 6.0);
+            canvas.fill_path(&handle, &vg::Paint::color(vg::Color::rgbf(0.6, 0.6, 0.6)));
+        }
+    }
+}
+
+impl SpectrumView {
+    /// Applies per-pixel peak hold with exponential decay to `spectrum`, using and updating
+    /// `self.peak_held_magnitudes` in the process. Returns the held values to draw this frame.
+    fn update_peak_hold(&self, spectrum: &[f32]) -> Vec<f32> {
+        let mut held = self.peak_held_magnitudes.borrow_mut();
+        if held.len() != spectrum.len() {
+            *held = spectrum.to_vec();
+            return held.clone();
+        }
+
+        for (held_value, &current_value) in held.iter_mut().zip(spectrum.iter()) {
+            *held_value = (*held_value - PEAK_HOLD_DECAY_DB).max(current_value);
+        }
+
+        held.clone()
+    }
+}
+
+/// Draws the filled band between the spectrum and `spectrum - gain_reduction`, making the gain
+/// reduction currently being applied at each frequency visible at a glance.
+fn draw_gain_reduction_band(
+    canvas: &mut Canvas,
+    spectrum: &[f32],
+    gain_reduction: &[f32],
+    x_for_pixel: impl Fn(usize) -> f32,
+    y_for_db: impl Fn(f32) -> f32,
+) {
+    if spectrum.is_empty() {
+        return;
+    }
+
+    let mut path = vg::Path::new();
+    for (pixel, &magnitude) in spectrum.iter().enumerate() {
+        let x = x_for_pixel(pixel);
+        let y = y_for_db(magnitude);
+        if pixel == 0 {
+            path.move_to(x, y);
+        } else {
+            path.line_to(x, y);
+        }
+    }
+    for (pixel, (&magnitude, &gr)) in spectrum.iter().zip(gain_reduction.iter()).enumerate().rev()
+    {
+        path.line_to(x_for_pixel(pixel), y_for_db(magnitude - gr));
+    }
+    path.close();
+
+    canvas.fill_path(&path, &vg::Paint::color(vg::Color::rgbaf(0.9, 0.3, 0.2, 0.35)));
+}
+
+fn draw_polyline(
+    canvas: &mut Canvas,
+    values: &[f32],
+    x_for_pixel: impl Fn(usize) -> f32,
+    y_for_db: impl Fn(f32) -> f32,
+    color: vg::Color,
+) {
+    if values.is_empty() {
+        return;
+    }
+
+    let mut path = vg::Path::new();
+    path.move_to(x_for_pixel(0), y_for_db(values[0]));
+    for (pixel, &value) in values.iter().enumerate().skip(1) {
+        path.line_to(x_for_pixel(pixel), y_for_db(value));
+    }
+
+    canvas.stroke_path(&path, &vg::Paint::color(color).with_line_width(1.5));
+}
+
+/// Resamples `NUM_BINS` bins down to one value per horizontal pixel. For each pixel this looks up
+/// its corresponding (possibly fractional) bin position and linearly interpolates between the two
+/// nearest bins, so every pixel gets a value even when there are more pixels than bins (as is the
+/// case at the default window size, where `NUM_BINS` is lower than the widget's pixel width).
+fn decimate_to_pixels(bins: &[f32; NUM_BINS], num_pixels: usize) -> Vec<f32> {
+    if num_pixels == 0 {
+        return Vec::new();
+    }
+
+    (0..num_pixels)
+        .map(|pixel| {
+            let t = pixel as f32 / (num_pixels - 1).max(1) as f32;
+            let bin_position = t * (NUM_BINS - 1) as f32;
+            let lower_bin = bin_position.floor() as usize;
+            let upper_bin = (lower_bin + 1).min(NUM_BINS - 1);
+            let fraction = bin_position - lower_bin as f32;
+
+            bins[lower_bin] + (bins[upper_bin] - bins[lower_bin]) * fraction
+        })
+        .collect()
+}
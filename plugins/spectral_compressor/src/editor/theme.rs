@@ -0,0 +1,74 @@
+// Spectral Compressor: an FFT based compressor
+// Copyright (C) 2021-2023 Robbert van der Helm
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use nih_plug::prelude::*;
+
+/// The built-in color palettes the editor can be switched between. The chosen value is persisted
+/// as a regular parameter so it is restored together with the rest of the plugin's state.
+#[derive(Enum, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum EditorTheme {
+    #[id = "dark"]
+    #[name = "Dark"]
+    Dark,
+    #[id = "light"]
+    #[name = "Light"]
+    Light,
+    #[id = "high_contrast"]
+    #[name = "High contrast"]
+    HighContrast,
+}
+
+impl EditorTheme {
+    /// The number of built-in themes, used to cycle between them in a fixed order.
+    pub const NUM_THEMES: usize = 3;
+}
+
+/// Returns the vizia stylesheet for a theme. These replace the hardcoded grays that used to live
+/// directly in `editor.rs`.
+pub fn stylesheet(theme: EditorTheme) -> &'static str {
+    match theme {
+        EditorTheme::Dark => DARK_THEME,
+        EditorTheme::Light => LIGHT_THEME,
+        EditorTheme::HighContrast => HIGH_CONTRAST_THEME,
+    }
+}
+
+const DARK_THEME: &str = r#"
+    .label {
+        color: #C2C2C2;
+    }
+    .row {
+        color: #C2C2C2;
+    }
+"#;
+
+const LIGHT_THEME: &str = r#"
+    .label {
+        color: #2B2B2B;
+    }
+    .row {
+        color: #2B2B2B;
+    }
+"#;
+
+const HIGH_CONTRAST_THEME: &str = r#"
+    .label {
+        color: #FFFFFF;
+    }
+    .row {
+        color: #FFFFFF;
+    }
+"#;
@@ -0,0 +1,412 @@
+// Spectral Compressor: an FFT based compressor
+// Copyright (C) 2021-2023 Robbert van der Helm
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use nih_plug::prelude::*;
+use realfft::num_complex::Complex32;
+use realfft::{RealFftPlanner, RealToComplex};
+use std::f32::consts::PI;
+use std::num::NonZeroU32;
+use std::sync::Arc;
+
+mod analyzer;
+mod editor;
+
+use analyzer::{analyzer_buffer, AnalyzerData, AnalyzerInput, AnalyzerOutput, NUM_BINS};
+use editor::theme::EditorTheme;
+
+/// The lowest frequency bin the analyzer computes a magnitude for.
+const ANALYZER_MIN_FREQUENCY: f32 = 20.0;
+/// The highest frequency bin the analyzer computes a magnitude for.
+const ANALYZER_MAX_FREQUENCY: f32 = 20_000.0;
+
+pub struct SpectralCompressor {
+    params: Arc<SpectralCompressorParams>,
+
+    sample_rate: f32,
+    /// The windowed FFT used to produce the analyzer's magnitude spectrum. Kept separate from
+    /// `SpectralCompressorParams` since it's pure audio-thread DSP state, not a parameter.
+    analyzer: SpectrumAnalyzer,
+    /// The producing end of the triple buffer used to ship [`AnalyzerData`] over to the editor.
+    /// Written to once per completed FFT block.
+    analyzer_input: AnalyzerInput,
+    /// The consuming end of the same triple buffer, handed off to the editor the first (and only)
+    /// time [`Plugin::editor()`] is called, see [`editor::create()`]. `Output` isn't `Clone`, so
+    /// this is `None` after that handoff.
+    analyzer_output: Option<AnalyzerOutput>,
+}
+
+impl Default for SpectralCompressor {
+    fn default() -> Self {
+        let (analyzer_input, analyzer_output) = analyzer_buffer();
+
+        Self {
+            params: Arc::new(SpectralCompressorParams::default()),
+
+            sample_rate: 1.0,
+            analyzer: SpectrumAnalyzer::new(),
+            analyzer_input,
+            analyzer_output: Some(analyzer_output),
+        }
+    }
+}
+
+#[derive(Params)]
+pub struct SpectralCompressorParams {
+    #[persist = "editor-state"]
+    editor_state: Arc<nih_plug_vizia::ViziaState>,
+
+    /// The editor's currently selected color theme. Persisted as a regular parameter so it's
+    /// restored together with the rest of the plugin's state.
+    #[id = "editor_theme"]
+    pub editor_theme: EnumParam<EditorTheme>,
+
+    #[nested(group = "global")]
+    pub global: GlobalParams,
+    #[nested(group = "threshold")]
+    pub threshold: ThresholdParams,
+    #[nested(group = "compressors")]
+    pub compressors: CompressorBankParams,
+}
+
+impl Default for SpectralCompressorParams {
+    fn default() -> Self {
+        Self {
+            editor_state: editor::default_state(),
+            editor_theme: EnumParam::new("Editor Theme", EditorTheme::Dark),
+
+            global: GlobalParams::default(),
+            threshold: ThresholdParams::default(),
+            compressors: CompressorBankParams::default(),
+        }
+    }
+}
+
+#[derive(Params)]
+pub struct GlobalParams {
+    #[id = "input_gain"]
+    pub input_gain: FloatParam,
+    #[id = "output_gain"]
+    pub output_gain: FloatParam,
+}
+
+impl Default for GlobalParams {
+    fn default() -> Self {
+        Self {
+            input_gain: FloatParam::new(
+                "Input Gain",
+                0.0,
+                FloatRange::Linear {
+                    min: -50.0,
+                    max: 50.0,
+                },
+            )
+            .with_unit(" dB"),
+            output_gain: FloatParam::new(
+                "Output Gain",
+                0.0,
+                FloatRange::Linear {
+                    min: -50.0,
+                    max: 50.0,
+                },
+            )
+            .with_unit(" dB"),
+        }
+    }
+}
+
+#[derive(Params)]
+pub struct ThresholdParams {
+    #[id = "threshold_offset_db"]
+    pub threshold_offset_db: FloatParam,
+}
+
+impl Default for ThresholdParams {
+    fn default() -> Self {
+        Self {
+            threshold_offset_db: FloatParam::new(
+                "Threshold Offset",
+                0.0,
+                FloatRange::Linear {
+                    min: -50.0,
+                    max: 50.0,
+                },
+            )
+            .with_unit(" dB"),
+        }
+    }
+}
+
+#[derive(Params)]
+pub struct CompressorBankParams {
+    #[nested(id_prefix = "upwards", group = "Upwards")]
+    pub upwards: CompressorParams,
+    #[nested(id_prefix = "downwards", group = "Downwards")]
+    pub downwards: CompressorParams,
+}
+
+impl Default for CompressorBankParams {
+    fn default() -> Self {
+        Self {
+            upwards: CompressorParams::new("Upwards"),
+            downwards: CompressorParams::new("Downwards"),
+        }
+    }
+}
+
+#[derive(Params)]
+pub struct CompressorParams {
+    #[id = "ratio"]
+    pub ratio: FloatParam,
+}
+
+impl CompressorParams {
+    fn new(prefix: &str) -> Self {
+        Self {
+            ratio: FloatParam::new(
+                format!("{prefix} Ratio"),
+                1.0,
+                FloatRange::Skewed {
+                    min: 1.0,
+                    max: 100.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            ),
+        }
+    }
+}
+
+impl Plugin for SpectralCompressor {
+    const NAME: &'static str = "Spectral Compressor";
+    const VENDOR: &'static str = "Robbert van der Helm";
+    const URL: &'static str = "https://github.com/robbert-vdh/nih-plug";
+    const EMAIL: &'static str = "mail@robbertvanderhelm.nl";
+    const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+    const AUDIO_IO_LAYOUTS: &'static [AudioIOLayout] = &[AudioIOLayout {
+        main_input_channels: NonZeroU32::new(2),
+        main_output_channels: NonZeroU32::new(2),
+        ..AudioIOLayout::const_default()
+    }];
+
+    const MIDI_INPUT: MidiConfig = MidiConfig::None;
+    const MIDI_OUTPUT: MidiConfig = MidiConfig::None;
+    const SAMPLE_ACCURATE_AUTOMATION: bool = true;
+
+    type SysExMessage = ();
+    type BackgroundTask = ();
+
+    fn params(&self) -> Arc<dyn Params> {
+        self.params.clone()
+    }
+
+    fn editor(&mut self, _async_executor: AsyncExecutor<Self>) -> Option<Box<dyn Editor>> {
+        let analyzer_output = self
+            .analyzer_output
+            .take()
+            .expect("editor() is only ever called once per plugin instance");
+
+        editor::create(
+            self.params.clone(),
+            self.params.editor_state.clone(),
+            analyzer_output,
+        )
+    }
+
+    fn initialize(
+        &mut self,
+        _audio_io_layout: &AudioIOLayout,
+        buffer_config: &BufferConfig,
+        _context: &mut impl InitContext<Self>,
+    ) -> bool {
+        self.sample_rate = buffer_config.sample_rate;
+
+        true
+    }
+
+    fn process(
+        &mut self,
+        buffer: &mut Buffer,
+        _aux: &mut AuxiliaryBuffers,
+        _context: &mut impl ProcessContext<Self>,
+    ) -> ProcessStatus {
+        if let Some(spectrum) = self
+            .analyzer
+            .process(buffer.as_slice_immutable()[0], self.sample_rate)
+        {
+            self.analyzer_input
+                .write(compute_analyzer_data(spectrum, &self.params));
+        }
+
+        ProcessStatus::Normal
+    }
+}
+
+/// Builds this block's [`AnalyzerData`] from a freshly computed magnitude `spectrum`, by laying
+/// the current threshold curve and per-bin gain reduction alongside it. `spectrum` is expected to
+/// already be log-spaced between [`ANALYZER_MIN_FREQUENCY`] and [`ANALYZER_MAX_FREQUENCY`], see
+/// [`SpectrumAnalyzer::process()`].
+fn compute_analyzer_data(spectrum: [f32; NUM_BINS], params: &SpectralCompressorParams) -> AnalyzerData {
+    let mut data = AnalyzerData {
+        spectrum,
+        ..AnalyzerData::default()
+    };
+
+    let threshold_db = params.threshold.threshold_offset_db.value();
+    let upwards_ratio = params.compressors.upwards.ratio.value();
+    let downwards_ratio = params.compressors.downwards.ratio.value();
+
+    for ((&magnitude_db, threshold_bin), gain_reduction_bin) in data
+        .spectrum
+        .iter()
+        .zip(data.threshold.iter_mut())
+        .zip(data.gain_reduction.iter_mut())
+    {
+        *threshold_bin = threshold_db;
+
+        // Bins above the threshold get pulled down by the downwards ratio, bins below it get
+        // pushed up by the upwards ratio. A ratio of 1 leaves the bin alone in both directions.
+        let excess_db = magnitude_db - threshold_db;
+        *gain_reduction_bin = if excess_db > 0.0 {
+            -(excess_db * (1.0 - 1.0 / downwards_ratio))
+        } else {
+            -excess_db * (1.0 - 1.0 / upwards_ratio)
+        };
+    }
+
+    data
+}
+
+/// The FFT size used by [`SpectrumAnalyzer`]. Must be a power of two.
+const ANALYZER_FFT_SIZE: usize = 2048;
+
+/// Produces the analyzer's magnitude spectrum with a real windowed FFT instead of a per-sample
+/// DFT, so the cost of the transform is paid once per [`ANALYZER_FFT_SIZE`] samples rather than
+/// once per sample.
+struct SpectrumAnalyzer {
+    fft: Arc<dyn RealToComplex<f32>>,
+    /// A Hann window, applied to `ring_buffer` before each FFT to reduce spectral leakage.
+    window: Vec<f32>,
+    /// Accumulates incoming samples until a full [`ANALYZER_FFT_SIZE`] block is ready.
+    ring_buffer: Vec<f32>,
+    /// Write position within `ring_buffer`.
+    ring_pos: usize,
+
+    fft_input: Vec<f32>,
+    fft_output: Vec<Complex32>,
+    fft_scratch: Vec<Complex32>,
+}
+
+impl SpectrumAnalyzer {
+    fn new() -> Self {
+        let fft = RealFftPlanner::<f32>::new().plan_fft_forward(ANALYZER_FFT_SIZE);
+        let window = (0..ANALYZER_FFT_SIZE)
+            .map(|n| {
+                0.5 * (1.0 - (2.0 * PI * n as f32 / (ANALYZER_FFT_SIZE - 1) as f32).cos())
+            })
+            .collect();
+
+        Self {
+            fft_input: fft.make_input_vec(),
+            fft_output: fft.make_output_vec(),
+            fft_scratch: fft.make_scratch_vec(),
+            fft,
+            window,
+            ring_buffer: vec![0.0; ANALYZER_FFT_SIZE],
+            ring_pos: 0,
+        }
+    }
+
+    /// Feeds `samples` into the ring buffer, running a windowed forward FFT every time it fills
+    /// up. Returns the resulting log-spaced magnitude spectrum, in dBFS, of the last block that
+    /// completed during this call; returns `None` for blocks that don't complete one, so the
+    /// caller can keep showing the previous frame instead of a half-filled one.
+    fn process(&mut self, samples: &[f32], sample_rate: f32) -> Option<[f32; NUM_BINS]> {
+        let mut result = None;
+
+        for &sample in samples {
+            self.ring_buffer[self.ring_pos] = sample;
+            self.ring_pos += 1;
+
+            if self.ring_pos == ANALYZER_FFT_SIZE {
+                self.ring_pos = 0;
+
+                for ((input, &sample), &window) in self
+                    .fft_input
+                    .iter_mut()
+                    .zip(self.ring_buffer.iter())
+                    .zip(self.window.iter())
+                {
+                    *input = sample * window;
+                }
+
+                self.fft
+                    .process_with_scratch(
+                        &mut self.fft_input,
+                        &mut self.fft_output,
+                        &mut self.fft_scratch,
+                    )
+                    .expect("the input/output/scratch buffers are sized by this same FFT plan");
+
+                result = Some(log_spaced_magnitudes(&self.fft_output, sample_rate));
+            }
+        }
+
+        result
+    }
+}
+
+/// Resamples the linearly spaced FFT output bins down to `NUM_BINS` magnitudes, in dBFS,
+/// log-spaced between [`ANALYZER_MIN_FREQUENCY`] and [`ANALYZER_MAX_FREQUENCY`].
+fn log_spaced_magnitudes(fft_output: &[Complex32], sample_rate: f32) -> [f32; NUM_BINS] {
+    let log_min = ANALYZER_MIN_FREQUENCY.ln();
+    let log_max = ANALYZER_MAX_FREQUENCY.ln();
+
+    let mut magnitudes = [0.0; NUM_BINS];
+    for (bin_idx, magnitude) in magnitudes.iter_mut().enumerate() {
+        let t = bin_idx as f32 / (NUM_BINS - 1) as f32;
+        let frequency = (log_min + t * (log_max - log_min)).exp();
+
+        let fft_bin = ((frequency / sample_rate) * ANALYZER_FFT_SIZE as f32).round() as usize;
+        let fft_bin = fft_bin.min(fft_output.len() - 1);
+
+        let linear_magnitude = fft_output[fft_bin].norm() / ANALYZER_FFT_SIZE as f32;
+        *magnitude = nih_plug::util::gain_to_db(linear_magnitude.max(1e-6));
+    }
+
+    magnitudes
+}
+
+impl ClapPlugin for SpectralCompressor {
+    const CLAP_ID: &'static str = "nl.robbertvanderhelm.spectral-compressor";
+    const CLAP_DESCRIPTION: Option<&'static str> =
+        Some("An FFT based compressor that allows for frequency-dependent gain reduction");
+    const CLAP_MANUAL_URL: Option<&'static str> = Some("https://github.com/robbert-vdh/nih-plug");
+    const CLAP_SUPPORT_URL: Option<&'static str> = None;
+    const CLAP_FEATURES: &'static [ClapFeature] = &[
+        ClapFeature::AudioEffect,
+        ClapFeature::Stereo,
+        ClapFeature::Compressor,
+    ];
+}
+
+impl Vst3Plugin for SpectralCompressor {
+    const VST3_CLASS_ID: [u8; 16] = *b"SpectralCmprssr.";
+    const VST3_SUBCATEGORIES: &'static [Vst3SubCategory] =
+        &[Vst3SubCategory::Fx, Vst3SubCategory::Dynamics];
+}
+
+nih_export_clap!(SpectralCompressor);
+nih_export_vst3!(SpectralCompressor);